@@ -14,10 +14,12 @@
 
 #![allow(missing_docs)]
 
-use std::ptr;
+use std::{mem, ptr};
 use winapi::{FLOAT, INT, UINT, UINT8, DXGI_FORMAT,
              DXGI_FORMAT_R16_UINT, DXGI_FORMAT_R32_UINT,
              D3D11_CLEAR_FLAG, D3D11_PRIMITIVE_TOPOLOGY, D3D11_VIEWPORT, D3D11_RECT,
+             D3D11_RESOURCE_DIMENSION_UNKNOWN, D3D11_RESOURCE_DIMENSION_TEXTURE2D,
+             D3D11_TEXTURE2D_DESC, ID3D11View, ID3D11Texture2D,
              ID3D11RasterizerState, ID3D11DepthStencilState, ID3D11BlendState};
 use core::{command, pso, shade, state, target, texture as tex};
 use core::{IndexType, VertexCount};
@@ -51,9 +53,33 @@ impl DataBuffer {
             size: data.len() as u32,
         }
     }
-    /// Return a reference to a stored data object.
-    pub fn get(&self, ptr: DataPointer) -> &[u8] {
-        &self.0[ptr.offset as usize .. (ptr.offset + ptr.size) as usize]
+    /// Return a reference to a stored data object, or `None` if the pointer
+    /// doesn't fit into the buffer.
+    pub fn get(&self, ptr: DataPointer) -> Option<&[u8]> {
+        let start = ptr.offset as usize;
+        match start.checked_add(ptr.size as usize) {
+            Some(end) if end <= self.0.len() => Some(&self.0[start .. end]),
+            _ => None,
+        }
+    }
+}
+
+/// Return the number of samples of the texture behind a view.
+fn get_sample_count(view: *mut ID3D11View) -> UINT {
+    unsafe {
+        let mut resource = ptr::null_mut();
+        (*view).GetResource(&mut resource);
+        let mut dim = D3D11_RESOURCE_DIMENSION_UNKNOWN;
+        (*resource).GetType(&mut dim);
+        let count = if dim == D3D11_RESOURCE_DIMENSION_TEXTURE2D {
+            let mut desc: D3D11_TEXTURE2D_DESC = mem::zeroed();
+            (*(resource as *mut ID3D11Texture2D)).GetDesc(&mut desc);
+            desc.SampleDesc.Count
+        } else {
+            1
+        };
+        (*resource).Release();
+        count
     }
 }
 
@@ -97,6 +123,8 @@ struct Cache {
     stencil_ref: UINT,
     blend: *const ID3D11BlendState,
     blend_ref: [FLOAT; 4],
+    srv_stages: shade::Usage,
+    pixel_targets: ([native::Rtv; MAX_COLOR_TARGETS], native::Dsv),
 }
 unsafe impl Send for Cache {}
 
@@ -109,6 +137,8 @@ impl Cache {
             stencil_ref: 0,
             blend: ptr::null(),
             blend_ref: [0.0; 4],
+            srv_stages: shade::Usage::empty(),
+            pixel_targets: ([native::Rtv(ptr::null_mut()); MAX_COLOR_TARGETS], native::Dsv(ptr::null_mut())),
         }
     }
 }
@@ -140,6 +170,17 @@ impl<P: Parser> CommandBuffer<P> {
         self.parser.parse(Command::SetDepthStencil(self.cache.depth_stencil, self.cache.stencil_ref));
         self.parser.parse(Command::SetBlend(self.cache.blend, self.cache.blend_ref, sample_mask));
     }
+
+    /// Bind null shader resources to all the stages that have any views bound.
+    fn unbind_resource_views(&mut self) {
+        for &stage in shade::STAGES.iter() {
+            if self.cache.srv_stages.contains(stage.into()) {
+                let views = [native::Srv(ptr::null_mut()); MAX_RESOURCE_VIEWS];
+                self.parser.parse(Command::BindShaderResources(stage, views));
+            }
+        }
+        self.cache.srv_stages = shade::Usage::empty();
+    }
 }
 
 impl<P: Parser> command::Buffer<Resources> for CommandBuffer<P> {
@@ -222,9 +263,15 @@ impl<P: Parser> command::Buffer<Resources> for CommandBuffer<P> {
                     count += 1;
                 }
             }
-            if count != 0 {
+            if count != 0 || self.cache.srv_stages.contains(mask) {
+                // an empty set still clears the views left over from the previous draw
                 self.parser.parse(Command::BindShaderResources(stage, views));
             }
+            if count != 0 {
+                self.cache.srv_stages.insert(mask);
+            } else {
+                self.cache.srv_stages.remove(mask);
+            }
         }
     }
 
@@ -282,6 +329,22 @@ impl<P: Parser> command::Buffer<Resources> for CommandBuffer<P> {
             }
         }
         let ds = pts.depth.unwrap_or(native::Dsv(ptr::null_mut()));
+        if (colors, ds) != self.cache.pixel_targets {
+            // D3D11 refuses to have a resource bound both as a shader input and as a target,
+            // so render-to-texture ping-pong (bloom, blur) needs the views unbound first
+            self.unbind_resource_views();
+            self.cache.pixel_targets = (colors, ds);
+            if !ds.0.is_null() {
+                let depth_samples = get_sample_count(ds.0 as *mut ID3D11View);
+                for color in colors.iter().filter(|c| !c.0.is_null()) {
+                    let color_samples = get_sample_count(color.0 as *mut ID3D11View);
+                    if color_samples != depth_samples {
+                        error!("Color target {:?} has {} samples, but depth target {:?} has {}",
+                            color, color_samples, ds, depth_samples);
+                    }
+                }
+            }
+        }
         self.parser.parse(Command::BindPixelTargets(colors, ds));
         self.parser.parse(Command::SetViewport(viewport));
     }
@@ -326,14 +389,25 @@ impl<P: Parser> command::Buffer<Resources> for CommandBuffer<P> {
     }
 
     fn clear_color(&mut self, target: native::Rtv, value: command::ClearColor) {
-        match value {
-            command::ClearColor::Float(data) => {
-                self.parser.parse(Command::ClearColor(target, data));
+        // D3D11 clears integer targets by converting the float values,
+        // so only the integers exactly representable as `f32` survive
+        const MAX_EXACT: u32 = 1 << 24;
+        let data = match value {
+            command::ClearColor::Float(data) => data,
+            command::ClearColor::Int(data) => {
+                if data.iter().any(|&v| (v as i64).abs() > MAX_EXACT as i64) {
+                    error!("Int clear value {:?} can't be represented exactly", data);
+                }
+                [data[0] as f32, data[1] as f32, data[2] as f32, data[3] as f32]
             },
-            _ => {
-                error!("Unable to clear int/uint target");
+            command::ClearColor::Uint(data) => {
+                if data.iter().any(|&v| v > MAX_EXACT) {
+                    error!("Uint clear value {:?} can't be represented exactly", data);
+                }
+                [data[0] as f32, data[1] as f32, data[2] as f32, data[3] as f32]
             },
-        }
+        };
+        self.parser.parse(Command::ClearColor(target, data));
     }
 
     fn clear_depth_stencil(&mut self, target: native::Dsv, depth: Option<target::Depth>,
@@ -367,3 +441,51 @@ impl<P: Parser> command::Buffer<Resources> for CommandBuffer<P> {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+    use core::command::{ClearColor, Buffer as CoreBuffer};
+    use core::texture as tex;
+    use {native, Buffer, Texture};
+    use super::{Command, CommandBuffer, DataBuffer, DataPointer, Parser};
+
+    struct CommandVec(Vec<Command>);
+    impl Parser for CommandVec {
+        fn reset(&mut self) {
+            self.0.clear();
+        }
+        fn parse(&mut self, com: Command) {
+            self.0.push(com);
+        }
+        fn update_buffer(&mut self, _: Buffer, _: &[u8], _: usize) {}
+        fn update_texture(&mut self, _: Texture, _: tex::Kind, _: Option<tex::CubeFace>, _: &[u8], _: tex::RawImageInfo) {}
+    }
+
+    #[test]
+    fn test_data_buffer_get() {
+        let mut buf = DataBuffer::new();
+        buf.add(&[1, 2]);
+        let ptr = buf.add(&[3, 4, 5]);
+        assert_eq!(buf.get(ptr), Some(&[3u8, 4, 5][..]));
+        assert_eq!(buf.get(DataPointer { offset: 4, size: 2 }), None);
+        assert_eq!(buf.get(DataPointer { offset: !0, size: 2 }), None);
+    }
+
+    #[test]
+    fn test_clear_integer_color() {
+        let mut cb: CommandBuffer<CommandVec> = CommandVec(Vec::new()).into();
+        let rtv = native::Rtv(ptr::null_mut());
+        cb.clear_color(rtv, ClearColor::Uint([1, 2, 3, 4]));
+        cb.clear_color(rtv, ClearColor::Int([-1, 0, 1, 2]));
+        assert_eq!(cb.parser.0.len(), 2);
+        match cb.parser.0[0] {
+            Command::ClearColor(_, data) => assert_eq!(data, [1.0, 2.0, 3.0, 4.0]),
+            ref other => panic!("Unexpected command {:?}", other),
+        }
+        match cb.parser.0[1] {
+            Command::ClearColor(_, data) => assert_eq!(data, [-1.0, 0.0, 1.0, 2.0]),
+            ref other => panic!("Unexpected command {:?}", other),
+        }
+    }
+}
@@ -268,3 +268,23 @@ pub fn map_dsv_flags(dsf: DepthStencilFlags) -> D3D11_DSV_FLAG {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use winapi::*;
+    use core::format::{ChannelType, Format, SurfaceType};
+    use super::{map_format, map_surface};
+
+    #[test]
+    fn test_packed_formats() {
+        let rgb10a2 = SurfaceType::R10_G10_B10_A2;
+        let rg11b10 = SurfaceType::R11_G11_B10;
+        assert_eq!(map_format(Format(rgb10a2, ChannelType::Unorm), false), Some(DXGI_FORMAT_R10G10B10A2_UNORM));
+        assert_eq!(map_format(Format(rgb10a2, ChannelType::Uint), true), Some(DXGI_FORMAT_R10G10B10A2_UINT));
+        assert_eq!(map_format(Format(rgb10a2, ChannelType::Float), false), None);
+        assert_eq!(map_surface(rgb10a2), Some(DXGI_FORMAT_R10G10B10A2_TYPELESS));
+        assert_eq!(map_format(Format(rg11b10, ChannelType::Float), true), Some(DXGI_FORMAT_R11G11B10_FLOAT));
+        assert_eq!(map_format(Format(rg11b10, ChannelType::Unorm), false), None);
+        assert_eq!(map_surface(rg11b10), Some(DXGI_FORMAT_R11G11B10_FLOAT));
+    }
+}
@@ -22,6 +22,16 @@ use {Buffer, Texture};
 
 pub fn update_buffer(context: *mut winapi::ID3D11DeviceContext, buffer: &Buffer,
                      data: &[u8], offset_bytes: usize) {
+    let size = unsafe {
+        let mut desc: winapi::D3D11_BUFFER_DESC = mem::zeroed();
+        (*(buffer.0).0).GetDesc(&mut desc);
+        desc.ByteWidth as usize
+    };
+    if offset_bytes + data.len() > size {
+        error!("Buffer {:?} of {} bytes can't be updated with {} bytes at offset {}",
+            buffer, size, data.len(), offset_bytes);
+        return
+    }
     let dst_resource = (buffer.0).0 as *mut winapi::ID3D11Resource;
     match buffer.1 {
         Usage::Immutable | Usage::CpuOnly(memory::READ) => {
@@ -64,6 +74,11 @@ pub fn update_texture(context: *mut winapi::ID3D11DeviceContext, texture: &Textu
     use core::texture::CubeFace::*;
     use winapi::UINT;
 
+    let expected = image.get_texel_count() * (image.format.0.get_total_bits() >> 3) as usize;
+    if data.len() != expected {
+        error!("Texture {:?} update expects {} bytes, got {}", texture, expected, data.len());
+        return
+    }
     let array_slice = match face {
         Some(PosX) => 0,
         Some(NegX) => 1,
@@ -206,13 +221,13 @@ pub fn process(ctx: *mut winapi::ID3D11DeviceContext, command: &command::Command
         SetBlend(blend, ref value, mask) => unsafe {
             (*ctx).OMSetBlendState(blend as *mut _, value, mask);
         },
-        UpdateBuffer(ref buffer, pointer, offset) => {
-            let data = data_buf.get(pointer);
-            update_buffer(ctx, buffer, data, offset);
+        UpdateBuffer(ref buffer, pointer, offset) => match data_buf.get(pointer) {
+            Some(data) => update_buffer(ctx, buffer, data, offset),
+            None => error!("Data {:?} for buffer {:?} is out of bounds", pointer, buffer),
         },
-        UpdateTexture(ref tex, kind, face, pointer, ref image) => {
-            let data = data_buf.get(pointer);
-            update_texture(ctx, tex, kind, face, data, image);
+        UpdateTexture(ref tex, kind, face, pointer, ref image) => match data_buf.get(pointer) {
+            Some(data) => update_texture(ctx, tex, kind, face, data, image),
+            None => error!("Data {:?} for texture {:?} is out of bounds", pointer, tex),
         },
         GenerateMips(ref srv) => unsafe {
             (*ctx).GenerateMips(srv.0);
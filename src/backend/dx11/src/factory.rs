@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{cmp, ptr, slice};
+use std::{cmp, mem, ptr, slice};
 use std::collections::BTreeMap as Map;
 use std::os::raw::c_void;
 use std::sync::Arc;
@@ -170,6 +170,31 @@ impl Factory {
         }
     }
 
+    /// Return the number of MSAA quality levels supported for a format with the given
+    /// number of samples. Zero means this sample count is not supported.
+    pub fn get_multisample_quality_levels(&self, format: core::format::Format, samples: texture::NumSamples)
+                                          -> winapi::UINT {
+        use data::map_format;
+        match map_format(format, true) {
+            Some(fm) => self.get_quality_levels(fm, samples as winapi::UINT),
+            None => 0,
+        }
+    }
+
+    fn get_quality_levels(&self, format: winapi::DXGI_FORMAT, samples: winapi::UINT) -> winapi::UINT {
+        let mut num_quality = 0;
+        let hr = unsafe {
+            (*self.device).CheckMultisampleQualityLevels(format, samples, &mut num_quality)
+        };
+        if winapi::SUCCEEDED(hr) {
+            num_quality
+        }else {
+            error!("Failed to check MSAA quality levels of {:?} with {} samples, error {:x}",
+                format, samples, hr);
+            0
+        }
+    }
+
     fn update_sub_data(&mut self, w: texture::Size, h: texture::Size, bpt: winapi::UINT)
                        -> *const winapi::D3D11_SUBRESOURCE_DATA {
         use winapi::UINT;
@@ -573,7 +598,7 @@ impl core::Factory<R> for Factory {
     fn create_texture_raw(&mut self, desc: texture::Info, hint: Option<core::format::ChannelType>,
                           data_opt: Option<&[&[u8]]>) -> Result<h::RawTexture<R>, texture::CreationError> {
         use core::texture::{AaMode, CreationError, Kind};
-        use data::{map_bind, map_usage, map_surface, map_format};
+        use data::{map_bind, map_usage, map_surface, map_format, map_anti_alias};
 
         let (usage, cpu_access) = map_usage(desc.usage);
         let tparam = TextureParam {
@@ -596,6 +621,20 @@ impl core::Factory<R> for Factory {
             cpu_access: cpu_access,
         };
 
+        match desc.kind {
+            Kind::D2(_, _, AaMode::Single) | Kind::D2Array(_, _, _, AaMode::Single) => (),
+            Kind::D2(_, _, aa) | Kind::D2Array(_, _, _, aa) => {
+                let sample_desc = map_anti_alias(aa);
+                let num_quality = self.get_quality_levels(tparam.format, sample_desc.Count);
+                if sample_desc.Quality >= num_quality {
+                    error!("Format {:?} with {} samples supports {} quality levels, requested {}",
+                        tparam.format, sample_desc.Count, num_quality, sample_desc.Quality);
+                    return Err(CreationError::Samples(aa))
+                }
+            },
+            _ => (),
+        }
+
         self.sub_data_array.clear();
         if let Some(data) = data_opt {
             for sub in data.iter() {
@@ -844,20 +883,32 @@ impl core::Factory<R> for Factory {
         use data::{FilterOp, map_function, map_filter, map_wrap};
 
         let op = if info.comparison.is_some() {FilterOp::Comparison} else {FilterOp::Product};
+        // D3D11 rejects a bias outside of [-16, 15.99] and an inverted LOD range.
+        // Note: the LOD range still applies to samplers without mipmap filtering.
+        let bias: f32 = info.lod_bias.into();
+        let clamped_bias = bias.max(-16.0).min(15.99);
+        if clamped_bias != bias {
+            warn!("Sampler LOD bias {} is clamped to {}", bias, clamped_bias);
+        }
+        let (mut min_lod, mut max_lod): (f32, f32) = (info.lod_range.0.into(), info.lod_range.1.into());
+        if min_lod > max_lod {
+            warn!("Sampler LOD range [{}, {}] is inverted, swapping", min_lod, max_lod);
+            mem::swap(&mut min_lod, &mut max_lod);
+        }
         let native_desc = winapi::D3D11_SAMPLER_DESC {
             Filter: map_filter(info.filter, op),
             AddressU: map_wrap(info.wrap_mode.0),
             AddressV: map_wrap(info.wrap_mode.1),
             AddressW: map_wrap(info.wrap_mode.2),
-            MipLODBias: info.lod_bias.into(),
+            MipLODBias: clamped_bias,
             MaxAnisotropy: match info.filter {
                 FilterMethod::Anisotropic(max) => max as winapi::UINT,
                 _ => 0,
             },
             ComparisonFunc: map_function(info.comparison.unwrap_or(core::state::Comparison::Always)),
             BorderColor: info.border.into(),
-            MinLOD: info.lod_range.0.into(),
-            MaxLOD: info.lod_range.1.into(),
+            MinLOD: min_lod,
+            MaxLOD: max_lod,
         };
 
         let mut raw_sampler = ptr::null_mut();